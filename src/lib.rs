@@ -2,9 +2,134 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
-use syn::{parse_macro_input,  ItemFn, ReturnType, AttributeArgs, NestedMeta, Meta, Lit};
+use syn::{parse_macro_input,  ItemFn, ReturnType, AttributeArgs, NestedMeta, Meta, Lit, Block, Expr};
+use syn::visit_mut::{self, VisitMut};
 use flexi_func_declarative::fb;
 
+/// Desugars an async-style function body into its blocking equivalent, mirroring
+/// the technique `maybe-async` uses to let a single body serve both worlds.
+///
+/// `.await` expressions collapse to their base expression, `async` blocks/closures
+/// are unwrapped to plain ones, and calls to a configurable executor primitive
+/// (default `block_on`, matched on the last path segment so e.g.
+/// `futures::executor::block_on(fut)` is recognized too) are stripped down to
+/// their single argument. A call whose target ends in `_async` and that is
+/// immediately `.await`ed and then `.unwrap()`ed or `?`-propagated collapses to
+/// a call on the sync twin (the suffix dropped, the trailing unwrap/`?` removed),
+/// since the sync twin returns the bare value rather than a `Result`; any other
+/// call to an `_async`-suffixed target keeps its name. The visitor
+/// recurses into nested blocks, match arms, loops and closures so it reaches
+/// every `.await` regardless of nesting, but it never looks inside string
+/// literals or macro invocations since `syn` does not walk into either.
+struct AsyncToSyncVisitor {
+    block_on_ident: String,
+}
+
+impl AsyncToSyncVisitor {
+    /// Recognizes `<call>.await.unwrap()` and `<call>.await?` where `<call>`
+    /// targets an `_async`-suffixed twin, and collapses the whole expression
+    /// down to a call on the sync twin. Returns `None` for anything else, in
+    /// which case the plain per-node rewrites below still apply.
+    fn collapse_awaited_twin_call(expr: &Expr) -> Option<Expr> {
+        let await_expr = match expr {
+            Expr::MethodCall(method_call) if method_call.method.to_string() == "unwrap" => {
+                match &*method_call.receiver {
+                    Expr::Await(await_expr) => await_expr,
+                    _ => return None,
+                }
+            }
+            Expr::Try(try_expr) => match &*try_expr.expr {
+                Expr::Await(await_expr) => await_expr,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let call = match &*await_expr.base {
+            Expr::Call(call) => call,
+            _ => return None,
+        };
+        let path = match &*call.func {
+            Expr::Path(expr_path) => expr_path,
+            _ => return None,
+        };
+        let targets_async_twin = path.path.segments.last()
+            .map_or(false, |segment| segment.ident.to_string().ends_with("_async"));
+        if !targets_async_twin {
+            return None;
+        }
+
+        let mut collapsed = call.clone();
+        if let Expr::Path(expr_path) = &mut *collapsed.func {
+            Self::strip_async_suffix(expr_path);
+        }
+        Some(Expr::Call(collapsed))
+    }
+
+    fn strip_async_suffix(path: &mut syn::ExprPath) {
+        if let Some(segment) = path.path.segments.last_mut() {
+            if let Some(stripped) = segment.ident.to_string().strip_suffix("_async") {
+                segment.ident = format_ident!("{}", stripped);
+            }
+        }
+    }
+}
+
+impl VisitMut for AsyncToSyncVisitor {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        if let Some(collapsed) = Self::collapse_awaited_twin_call(expr) {
+            *expr = collapsed;
+            self.visit_expr_mut(expr);
+            return;
+        }
+
+        visit_mut::visit_expr_mut(self, expr);
+
+        match expr {
+            Expr::Await(expr_await) => {
+                *expr = (*expr_await.base).clone();
+            }
+            Expr::Async(expr_async) => {
+                let block = &expr_async.block;
+                *expr = syn::parse_quote! { #block };
+            }
+            Expr::Closure(closure) if closure.asyncness.is_some() => {
+                closure.asyncness = None;
+            }
+            Expr::Call(call) => {
+                if let Expr::Path(path) = &mut *call.func {
+                    let is_block_on = path.path.segments.last()
+                        .map_or(false, |segment| segment.ident.to_string() == self.block_on_ident);
+                    if is_block_on && call.args.len() == 1 {
+                        *expr = call.args.first().unwrap().clone();
+                    }
+                    // Note: the `_async`-suffix rewrite is intentionally *not*
+                    // applied here. It only makes sense for a call that is
+                    // awaited-and-unwrapped/`?`-propagated, which is already
+                    // handled above by `collapse_awaited_twin_call`; stripping
+                    // the suffix off every bare call (awaited or not, a twin
+                    // or not) would rewrite calls this visitor has no business
+                    // touching.
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parsed form of the `instrument` attribute option: which fields to omit from the
+/// span and at what `tracing::Level` to open it.
+struct InstrumentConfig {
+    skip: Vec<syn::Ident>,
+    level: syn::Ident,
+}
+
+impl Default for InstrumentConfig {
+    fn default() -> Self {
+        InstrumentConfig { skip: Vec::new(), level: format_ident!("INFO") }
+    }
+}
+
 #[allow(unused_macros)]
 /// The `ff` proc macro (Flexi Function) simplifies the generation of asynchronous versions of a synchronous function in Rust.
 /// By transforming a synchronous function into both synchronous and asynchronous versions, where the actual async stuff is included inside the fb! macro.
@@ -16,6 +141,21 @@ use flexi_func_declarative::fb;
 ///
 /// - `async_fn_name`: Overrides the default name of the generated asynchronous function.
 /// - `error_type`: Specifies a custom error type for the asynchronous function. The type must implement `From` for any error types that the function body can emit.
+/// - `offload`: Moves the body onto a runtime's blocking thread pool instead of running it inline on the async executor. Accepts `"tokio"` or `"async-std"` to pick the runtime (`"blocking"` is an alias for `"tokio"`). When set, every generic type parameter gets `Send + 'static` bounds added on the async twin only, since that's the one moving the body into a spawned closure; the sync twin's generics are left untouched. Combined with `source = "async"`, the body is not offloaded and runs inline as a future, though the bounds are still added.
+/// - `source`: Set to `"async"` to author the body once using real `async`/`.await` instead of plain sync code, returning the plain (non-`Result`) type just like an ordinary sync body would. The async function keeps that body untouched and wraps its result in `Ok(...)` to satisfy the declared `Result<_, error_type>` return; the sync function gets a mechanically desugared copy with every `.await` and `async` block stripped out. Defaults to `"sync"`, preserving today's behavior of sharing one body verbatim.
+/// - `block_on`: Overrides the name of the executor primitive stripped out by `source = "async"` desugaring (e.g. a call to `futures::executor::block_on(fut)` becomes just `fut`). Defaults to `"block_on"`.
+/// - `instrument`: Wraps both generated functions in a `tracing` span, so they're observable without hand-writing `#[tracing::instrument]` on each. The sync function enters the span around its body; the async function attaches the span to its future with `tracing::Instrument::instrument` so it follows the future across suspension points instead of being held across `.await`. Span fields default to every parameter ident, `skip(a, b)` omits specific ones, and `level = "debug"` picks the span level (default `info`). The async span is named after `#async_fn_name`, which already distinguishes it from the sync span named after `#ident`.
+///
+/// # Feature-Gated Single-Variant Emission
+///
+/// Both generated functions are always attached with `#[cfg(...)]` guards keyed off the
+/// `is_sync` and `is_async` crate features, so a dependent crate can compile in only the
+/// variant it needs instead of carrying both unconditionally:
+///
+/// - Neither feature enabled: both functions are emitted (today's default behavior).
+/// - Only `is_sync` enabled: only `#ident` is emitted.
+/// - Only `is_async` enabled: only `#async_fn_name` is emitted.
+/// - Both enabled: both functions are emitted.
 ///
 /// # Usage
 ///
@@ -58,6 +198,51 @@ use flexi_func_declarative::fb;
 /// // async fn custom_async(s: String) -> Result<usize, Box<dyn std::error::Error + Send + Sync + 'static>> { ... }
 /// ```
 ///
+/// ## Writing the Body Once in Async Style
+///
+/// ```
+/// #[ff(source = "async")]
+/// async fn example_async_source(url: String) -> usize {
+///     fetch(url).await.len()
+/// }
+///
+/// // This generates:
+/// // fn example_async_source(url: String) -> usize { fetch(url).len() }
+/// // async fn example_async_source_async(url: String) -> Result<usize, ...> { Ok(fetch(url).await.len()) }
+/// ```
+///
+/// ## Instrumenting Both Twins with a Tracing Span
+///
+/// ```
+/// #[ff(instrument(skip(password), level = "debug"))]
+/// fn example_instrumented(user: String, password: String) -> bool {
+///     user == "admin"
+/// }
+///
+/// // This generates:
+/// // fn example_instrumented(user: String, password: String) -> bool {
+/// //     let __span = tracing::span!(tracing::Level::DEBUG, "example_instrumented", user = ?user);
+/// //     let __enter = __span.enter();
+/// //     user == "admin"
+/// // }
+/// // async fn example_instrumented_async(user: String, password: String) -> Result<bool, ...> { ... }
+/// ```
+///
+/// ## Offloading the Sync Body onto a Blocking Pool
+///
+/// ```
+/// #[ff(offload = "tokio")]
+/// fn example_blocking(s: String) -> usize {
+///     s.len()
+/// }
+///
+/// // This generates:
+/// // fn example_blocking(s: String) -> usize { ... }
+/// // async fn example_blocking_async(s: String) -> Result<usize, Box<dyn std::error::Error + Send + Sync + 'static>> {
+/// //     tokio::task::spawn_blocking(move || { ... }).await.map_err(|e| e.into())
+/// // }
+/// ```
+///
 /// # Note
 ///
 /// The macro assumes that the synchronous version of the function does not return a `Result` type. If it does, and no `error_type` attribute is provided, the default error type for the asynchronous version is `Box<dyn std::error::Error + Send + Sync + 'static>`.
@@ -85,10 +270,88 @@ pub fn ff(attr: TokenStream, item: TokenStream) -> TokenStream {
         _ => None,
     });
 
+    // `offload = "tokio" | "async-std" | "blocking"` moves the sync body onto the
+    // runtime's blocking pool instead of running it inline on the async executor.
+    // The two runtimes disagree on what awaiting the spawned task yields: tokio's
+    // `JoinHandle` awaits to `Result<T, JoinError>`, while async-std's awaits
+    // straight to `T`, so callers further down branch on `is_async_std` instead
+    // of sharing one `.map_err` tail for both.
+    let offload_runtime = attrs.iter().find_map(|attr| match attr {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("offload") => match &nv.lit {
+            Lit::Str(lit_str) => match lit_str.value().as_str() {
+                "async-std" => Some((quote! { async_std::task::spawn_blocking }, true)),
+                // "blocking" is a runtime-agnostic alias; tokio is the default executor.
+                "tokio" | "blocking" => Some((quote! { tokio::task::spawn_blocking }, false)),
+                other => panic!("unsupported `offload` runtime: {}", other),
+            },
+            _ => None,
+        },
+        _ => None,
+    });
+
+    // `source = "async"` means the body below is already written with real
+    // `async`/`.await` and should be desugared into the sync twin instead of
+    // being shared verbatim.
+    let source_is_async = attrs.iter().any(|attr| matches!(attr,
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("source")
+            && matches!(&nv.lit, Lit::Str(s) if s.value() == "async")
+    ));
+
+    let block_on_ident = attrs.iter().find_map(|attr| match attr {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("block_on") => match &nv.lit {
+            Lit::Str(lit_str) => Some(lit_str.value()),
+            _ => None,
+        },
+        _ => None,
+    }).unwrap_or_else(|| "block_on".to_string());
+
+    // `instrument` (bare, or with `skip(...)`/`level = "..."` sub-arguments) wraps
+    // both generated functions in a `tracing` span.
+    let instrument = attrs.iter().find_map(|attr| match attr {
+        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("instrument") => {
+            Some(InstrumentConfig::default())
+        }
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("instrument") => {
+            let mut config = InstrumentConfig::default();
+            for nested in &list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::List(skip_list)) if skip_list.path.is_ident("skip") => {
+                        config.skip = skip_list.nested.iter().filter_map(|item| match item {
+                            NestedMeta::Meta(Meta::Path(p)) => p.get_ident().cloned(),
+                            _ => None,
+                        }).collect();
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("level") => {
+                        if let Lit::Str(lit_str) = &nv.lit {
+                            config.level = format_ident!("{}", lit_str.value().to_uppercase());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some(config)
+        }
+        _ => None,
+    });
+
     // Extracting essential components from the input function
     let ItemFn { attrs, vis, sig, block } = input_fn;
     let syn::Signature { ident, inputs, output, generics, .. } = sig;
 
+    // Offloading moves the body into a spawned closure, so the async twin's
+    // captured generics must be `Send + 'static` for the spawn call to
+    // type-check. The sync twin never touches the closure, so it keeps the
+    // original, unconstrained generics on its own copy.
+    let mut async_generics = generics.clone();
+    if offload_runtime.is_some() {
+        for param in async_generics.params.iter_mut() {
+            if let syn::GenericParam::Type(type_param) = param {
+                type_param.bounds.push(syn::parse_quote!(Send));
+                type_param.bounds.push(syn::parse_quote!('static));
+            }
+        }
+    }
+
     // Determining the return type and adjusting for async transformation
     let async_fn_name = async_fn_name_override.unwrap_or_else(|| format_ident!("{}_async", ident));
     let return_type = match output {
@@ -100,20 +363,232 @@ pub fn ff(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     let async_return_type = quote! { Result<#return_type, #error_type> };
 
+    // When the body is authored in async style, the sync twin is produced by
+    // mechanically stripping `.await`/`async` out of a clone; the async twin
+    // keeps the original tree untouched and is used as-is below.
+    let sync_block: Block = if source_is_async {
+        let mut desugared = (*block).clone();
+        AsyncToSyncVisitor { block_on_ident: block_on_ident.clone() }.visit_block_mut(&mut desugared);
+        desugared
+    } else {
+        (*block).clone()
+    };
+
+    // Span fields default to every parameter ident, skipping anything named in
+    // `skip(...)`; non-`Debug` or oversized args are expected to opt out that way.
+    let span_fields: Vec<_> = instrument.as_ref().map(|config| {
+        inputs.iter().filter_map(|arg| match arg {
+            syn::FnArg::Typed(pat_type) => match &*pat_type.pat {
+                syn::Pat::Ident(pat_ident) if !config.skip.contains(&pat_ident.ident) => {
+                    let field_ident = &pat_ident.ident;
+                    Some(quote! { #field_ident = ?#field_ident })
+                }
+                _ => None,
+            },
+            _ => None,
+        }).collect()
+    }).unwrap_or_default();
+
+    // Without `offload`, the body runs inline on whatever task polls the future.
+    // With `offload`, it is moved onto the runtime's blocking pool instead.
+    // A `source = "async"` body is a real async block, so it always runs
+    // inline and `offload` does not apply to it.
+    let async_future = if source_is_async {
+        quote! { async move { #block } }
+    } else {
+        match &offload_runtime {
+            Some((spawn_blocking, _)) => quote! { #spawn_blocking(move || { #block }) },
+            None => quote! { async move { #block } },
+        }
+    };
+
+    // `tracing::Instrument::instrument` attaches the span to the future itself, so
+    // it is entered/exited around every poll rather than just held across `.await`.
+    // The span is built into a local *before* `#async_future` is constructed,
+    // since `#async_future` moves every captured parameter (into the `async move`
+    // block or the spawned closure) and the span's default fields borrow those
+    // same parameters by `?ident` — building the span first avoids borrowing
+    // something that's about to be moved out from under it.
+    let async_future = match &instrument {
+        Some(config) => {
+            let level = &config.level;
+            quote! {
+                {
+                    let __span = tracing::span!(tracing::Level::#level, stringify!(#async_fn_name), #(#span_fields),*);
+                    tracing::Instrument::instrument(#async_future, __span)
+                }
+            }
+        }
+        None => async_future,
+    };
+
+    // Only tokio's `JoinHandle` awaits to a `Result`; its join error (if the
+    // spawned closure panics) is mapped into the chosen error type. An inline
+    // body (plain or `source = "async"`) and async-std's `JoinHandle` both
+    // yield the plain return value, so they are just wrapped in `Ok` to match
+    // `async_return_type`.
+    let is_tokio_offload = !source_is_async && matches!(&offload_runtime, Some((_, false)));
+    let async_body = if is_tokio_offload {
+        quote! { #async_future.await.map_err(|e| e.into()) }
+    } else {
+        quote! { Ok(#async_future.await) }
+    };
+
+    // With `instrument`, the sync function opens and enters its own span around
+    // the body instead of relying on the caller to have wrapped the call site.
+    let sync_fn_body = match &instrument {
+        Some(config) => {
+            let level = &config.level;
+            quote! {
+                let __span = tracing::span!(tracing::Level::#level, stringify!(#ident), #(#span_fields),*);
+                let __enter = __span.enter();
+                #sync_block
+            }
+        }
+        None => quote! { #sync_block },
+    };
+
+    // Each variant is gated so that a dependent crate can select `is_sync` or
+    // `is_async` alone and only pay for the function it actually needs; with
+    // neither feature set both cfgs are true, preserving today's behavior.
+    let sync_cfg = quote! { #[cfg(any(feature = "is_sync", not(feature = "is_async")))] };
+    let async_cfg = quote! { #[cfg(any(feature = "is_async", not(feature = "is_sync")))] };
+
     // Generating both synchronous and asynchronous versions of the function
     let gen = quote! {
+        #sync_cfg
         #( #attrs )*
         #vis fn #ident #generics (#inputs) -> #return_type {
-            #block
+            #sync_fn_body
         }
 
+        #async_cfg
         #( #attrs )*
-        #vis async fn #async_fn_name #generics (#inputs) -> #async_return_type {
-            async move {
-                #block
-            }.await.map_err(|e| e.into())
+        #vis async fn #async_fn_name #async_generics (#inputs) -> #async_return_type {
+            #async_body
         }
     };
 
     gen.into()
 }
+
+/// A companion test attribute that exercises an `ff`-generated sync/async pair
+/// from a single `async`-style test body, the same way `#[actix_rt::test]`
+/// bootstraps a runtime around an `async fn` rather than requiring callers to
+/// do it by hand.
+///
+/// Given an `async fn`, this generates two `#[test]` functions: one under the
+/// original name that runs the body with every `.await` desugared away (the
+/// blocking path, via the same desugaring the `source = "async"` mode of [`ff`]
+/// uses), and one suffixed `_async` that boots a runtime and drives the
+/// original body to completion (the async path). Since `ff` already produces
+/// paired sync/async functions, a test body that calls both twins and asserts
+/// on their results gets one-line coverage of both and catches the two paths
+/// silently diverging. A non-`async fn` is emitted as a single `#[test]`
+/// unchanged.
+///
+/// A call to an `_async`-suffixed twin that is immediately `.await`ed and then
+/// `.unwrap()`ed or `?`-propagated collapses, on the blocking path, straight to
+/// a call on the sync twin (see [`ff`]'s desugaring visitor) — so the twin can
+/// be called the same way on both paths. That collapsing only looks at plain
+/// expressions, not inside macro invocations, so keep such calls out of
+/// `assert!`/`assert_eq!` arguments directly; bind the result to a `let` first.
+///
+/// # Attributes
+///
+/// - `runtime`: Selects the runtime used to drive the async path. Accepts
+///   `"tokio"` (default) or `"async-std"`.
+/// - `block_on`: Overrides the name of the executor primitive stripped out of
+///   the blocking path, same as [`ff`]'s `block_on` option. Defaults to `"block_on"`.
+///
+/// # Usage
+///
+/// ```
+/// use flexi_func::{ff, test};
+///
+/// #[ff]
+/// fn example_sync(s: String) -> usize {
+///     s.len()
+/// }
+///
+/// #[test]
+/// async fn checks_both_twins() {
+///     let via_sync = example_sync("hi".to_string());
+///     let via_async = example_sync_async("hi".to_string()).await.unwrap();
+///     assert_eq!(via_sync, via_async);
+/// }
+///
+/// // This generates:
+/// // #[test]
+/// // fn checks_both_twins() {
+/// //     let via_sync = example_sync("hi".to_string());
+/// //     let via_async = example_sync("hi".to_string()); // `.await.unwrap()` collapsed with the call
+/// //     assert_eq!(via_sync, via_async);
+/// // }
+/// // #[test]
+/// // fn checks_both_twins_async() {
+/// //     tokio::runtime::Runtime::new().unwrap().block_on(async move {
+/// //         let via_sync = example_sync("hi".to_string());
+/// //         let via_async = example_sync_async("hi".to_string()).await.unwrap();
+/// //         assert_eq!(via_sync, via_async);
+/// //     });
+/// // }
+/// ```
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attrs = parse_macro_input!(attr as AttributeArgs);
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    let runtime_block_on = attrs.iter().find_map(|attr| match attr {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("runtime") => match &nv.lit {
+            Lit::Str(lit_str) => match lit_str.value().as_str() {
+                "async-std" => Some(quote! { async_std::task::block_on }),
+                "tokio" => Some(quote! { tokio::runtime::Runtime::new().unwrap().block_on }),
+                other => panic!("unsupported `runtime` for ff::test: {}", other),
+            },
+            _ => None,
+        },
+        _ => None,
+    }).unwrap_or_else(|| quote! { tokio::runtime::Runtime::new().unwrap().block_on });
+
+    let block_on_ident = attrs.iter().find_map(|attr| match attr {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("block_on") => match &nv.lit {
+            Lit::Str(lit_str) => Some(lit_str.value()),
+            _ => None,
+        },
+        _ => None,
+    }).unwrap_or_else(|| "block_on".to_string());
+
+    let ItemFn { attrs, vis, sig, block } = input_fn;
+
+    if sig.asyncness.is_none() {
+        return quote! {
+            #( #attrs )*
+            #[test]
+            #vis #sig #block
+        }.into();
+    }
+
+    let mut sync_sig = sig.clone();
+    sync_sig.asyncness = None;
+    let async_fn_ident = format_ident!("{}_async", sig.ident);
+
+    let mut blocking_block = (*block).clone();
+    AsyncToSyncVisitor { block_on_ident }.visit_block_mut(&mut blocking_block);
+
+    quote! {
+        #( #attrs )*
+        #[test]
+        #vis #sync_sig {
+            #blocking_block
+        }
+
+        #( #attrs )*
+        #[test]
+        #vis fn #async_fn_ident() {
+            #runtime_block_on(async move {
+                #block
+            });
+        }
+    }.into()
+}
@@ -0,0 +1,18 @@
+//! Exercises an `#[ff]`-generated sync/async pair through `#[flexi_func::test]`,
+//! so the two code paths are actually built and run instead of only appearing
+//! in doc-comment prose.
+
+use flexi_func::{ff, test};
+
+#[ff]
+fn double(n: usize) -> usize {
+    n * 2
+}
+
+#[test]
+async fn sync_and_async_twins_agree() {
+    let via_sync = double(21);
+    let via_async = double_async(21).await.unwrap();
+    assert_eq!(via_sync, via_async);
+    assert_eq!(via_sync, 42);
+}